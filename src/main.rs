@@ -1,9 +1,11 @@
-use std::{error::Error, fmt, fs, io, str::FromStr};
+use std::{error::Error, fmt, fs, io, path::Path, str::FromStr};
 
-use cairo::{Context, Format, ImageSurface};
+use cairo::{Format, ImageSurface};
 use clap::Parser;
 use jpeg_decoder::{Decoder, PixelFormat};
 use jpeg_encoder::Encoder;
+use ravif::{ColorSpace as RavifColorSpace, Encoder as AvifEncoder, Img};
+use rgb::RGBA8;
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -17,6 +19,27 @@ struct Args {
 
     #[clap(long, default_value_t = 60)]
     quality: u8,
+
+    /// AVIF-only: the pixel representation ravif encodes internally. This is
+    /// a compression-efficiency tradeoff, not a color space / transfer
+    /// function choice (both are decoded back to the same RGB on display).
+    #[clap(long, value_enum, default_value = "ycbcr")]
+    pixel_format: AvifPixelFormat,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum AvifPixelFormat {
+    Rgb,
+    Ycbcr,
+}
+
+impl From<AvifPixelFormat> for RavifColorSpace {
+    fn from(pf: AvifPixelFormat) -> RavifColorSpace {
+        match pf {
+            AvifPixelFormat::Rgb => RavifColorSpace::RGB,
+            AvifPixelFormat::Ycbcr => RavifColorSpace::YCbCr,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +47,7 @@ struct Color {
     r: u8,
     b: u8,
     g: u8,
+    a: u8,
 }
 
 impl Color {
@@ -32,6 +56,7 @@ impl Color {
             r: 255,
             g: 255,
             b: 255,
+            a: 255,
         }
     }
 
@@ -39,59 +64,336 @@ impl Color {
         s.parse::<Color>().map_err(|e| e.to_string())
     }
 
-    fn r(&self) -> f64 {
-        self.r as f64 / 255.0
-    }
+    fn from_hex(hex: &str) -> Option<Color> {
+        if !hex.is_ascii() {
+            return None;
+        }
 
-    fn g(&self) -> f64 {
-        self.g as f64 / 255.0
+        let expand = |c: char| c.to_digit(16).map(|d| (d * 17) as u8);
+        let byte = |s: &str| u8::from_str_radix(s, 16).ok();
+        let mut chars = hex.chars();
+        match hex.len() {
+            3 => Some(Color {
+                r: expand(chars.next()?)?,
+                g: expand(chars.next()?)?,
+                b: expand(chars.next()?)?,
+                a: 255,
+            }),
+            6 => Some(Color {
+                r: byte(&hex[0..2])?,
+                g: byte(&hex[2..4])?,
+                b: byte(&hex[4..6])?,
+                a: 255,
+            }),
+            8 => Some(Color {
+                r: byte(&hex[0..2])?,
+                g: byte(&hex[2..4])?,
+                b: byte(&hex[4..6])?,
+                a: byte(&hex[6..8])?,
+            }),
+            _ => None,
+        }
     }
+}
 
-    fn b(&self) -> f64 {
-        self.b as f64 / 255.0
+/// A small set of CSS named colors, enough to cover the common
+/// case of typing `--background white` instead of `--background #ffffff`.
+const NAMED_COLORS: &[(&str, u8, u8, u8)] = &[
+    ("black", 0, 0, 0),
+    ("white", 255, 255, 255),
+    ("red", 255, 0, 0),
+    ("green", 0, 128, 0),
+    ("blue", 0, 0, 255),
+    ("yellow", 255, 255, 0),
+    ("cyan", 0, 255, 255),
+    ("magenta", 255, 0, 255),
+    ("gray", 128, 128, 128),
+    ("grey", 128, 128, 128),
+    ("silver", 192, 192, 192),
+    ("maroon", 128, 0, 0),
+    ("olive", 128, 128, 0),
+    ("lime", 0, 255, 0),
+    ("teal", 0, 128, 128),
+    ("navy", 0, 0, 128),
+    ("purple", 128, 0, 128),
+    ("orange", 255, 165, 0),
+    ("pink", 255, 192, 203),
+    ("brown", 165, 42, 42),
+    ("gold", 255, 215, 0),
+    ("coral", 255, 127, 80),
+    ("salmon", 250, 128, 114),
+    ("khaki", 240, 230, 140),
+    ("lavender", 230, 230, 250),
+    ("beige", 245, 245, 220),
+    ("ivory", 255, 255, 240),
+    ("plum", 221, 160, 221),
+    ("orchid", 218, 112, 214),
+    ("tan", 210, 180, 140),
+    ("turquoise", 64, 224, 208),
+    ("chocolate", 210, 105, 30),
+    ("crimson", 220, 20, 60),
+    ("indigo", 75, 0, 130),
+    ("violet", 238, 130, 238),
+];
+
+fn named_color(s: &str) -> Option<Color> {
+    if s.eq_ignore_ascii_case("transparent") {
+        return Some(Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        });
     }
+    NAMED_COLORS
+        .iter()
+        .find(|(name, ..)| s.eq_ignore_ascii_case(name))
+        .map(|&(_, r, g, b)| Color {
+            r,
+            g,
+            b,
+            a: 255,
+        })
 }
 
 impl FromStr for Color {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.starts_with('#') || s.len() != 7 {
-            return Err(format!("Invalid color: {}", s).into());
+        if let Some(hex) = s.strip_prefix('#') {
+            return Color::from_hex(hex).ok_or_else(|| format!("Invalid color: {}", s).into());
         }
-        let r = u8::from_str_radix(&s[1..3], 16).map_err(|_| format!("Invalid color: {}", s))?;
-        let g = u8::from_str_radix(&s[3..5], 16).map_err(|_| format!("Invalid color: {}", s))?;
-        let b = u8::from_str_radix(&s[5..7], 16).map_err(|_| format!("Invalid color: {}", s))?;
-        Ok(Color { r, g, b })
+        named_color(s).ok_or_else(|| format!("Invalid color: {}", s).into())
     }
 }
 
+fn is_shorthand_channel(v: u8) -> bool {
+    v & 0x0f == v >> 4
+}
+
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        if self.a != 255 {
+            write!(f, "#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        } else if is_shorthand_channel(self.r)
+            && is_shorthand_channel(self.g)
+            && is_shorthand_channel(self.b)
+        {
+            write!(f, "#{:x}{:x}{:x}", self.r >> 4, self.g >> 4, self.b >> 4)
+        } else {
+            write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        }
+    }
+}
+
+/// A source of pixels that can be produced one scanline at a time, so callers
+/// don't need the whole image resident in memory at once.
+trait PixelSource {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// Returns the BGRA bytes for scanline `y` (length `width() * 4`).
+    fn row(&self, y: usize) -> Vec<u8>;
+}
+
+/// Lazily composites a decoded photo over a solid background at a given
+/// opacity, producing BGRA scanlines on demand instead of materializing a
+/// second full-resolution surface up front. Scanlines are premultiplied by
+/// alpha, as `cairo::Format::ARgb32` requires.
+struct Composite {
+    data: Vec<u8>,
+    width: usize,
+    height: usize,
+    stride: usize,
+    has_alpha: bool,
+    background: Color,
+    opacity: f64,
+}
+
+impl Composite {
+    /// `output_has_alpha` should be `false` for formats that can't represent
+    /// transparency (JPEG), in which case the background is treated as
+    /// opaque rather than silently flattening a translucent background
+    /// towards black.
+    fn new(
+        photo: ImageSurface,
+        mut background: Color,
+        opacity: f64,
+        output_has_alpha: bool,
+    ) -> Result<Composite, Box<dyn Error>> {
+        if !output_has_alpha {
+            background.a = 255;
+        }
+
+        let width = photo.width() as usize;
+        let height = photo.height() as usize;
+        let stride = photo.stride() as usize;
+        let has_alpha = photo.format() == Format::ARgb32;
+        let data = photo.take_data()?.as_ref().to_vec();
+        Ok(Composite {
+            data,
+            width,
+            height,
+            stride,
+            has_alpha,
+            background,
+            opacity,
+        })
+    }
+
+    /// Materializes the full composite as an ARGB32 surface, for encoders
+    /// (PNG, AVIF) that need the whole image up front.
+    fn to_surface(&self) -> Result<ImageSurface, Box<dyn Error>> {
+        let mut argb = Vec::with_capacity(self.width * self.height * 4);
+        for y in 0..self.height {
+            argb.extend_from_slice(&self.row(y));
+        }
+        Ok(ImageSurface::create_for_data(
+            argb,
+            Format::ARgb32,
+            self.width as i32,
+            self.height as i32,
+            Format::ARgb32.stride_for_width(self.width as u32)?,
+        )?)
     }
 }
 
+/// Porter-Duff "over", alpha component: the result's alpha when compositing
+/// a source of `src_alpha` over a background of `bg_alpha`.
+fn composite_alpha(src_alpha: f64, bg_alpha: f64) -> f64 {
+    src_alpha + bg_alpha * (1.0 - src_alpha)
+}
+
+/// Porter-Duff "over" for a single premultiplied output channel byte.
+fn composite_channel(src: u8, bg: u8, src_alpha: f64, bg_alpha: f64) -> u8 {
+    (src as f64 * src_alpha + bg as f64 * bg_alpha * (1.0 - src_alpha)).round() as u8
+}
+
+impl PixelSource for Composite {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, y: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.width * 4);
+        let row_start = y * self.stride;
+        let bg_alpha = self.background.a as f64 / 255.0;
+        for x in 0..self.width {
+            let i = row_start + x * 4;
+            let a = if self.has_alpha { self.data[i + 3] } else { 255 };
+            // ARGB32 sources are premultiplied; recover straight channels
+            // (a no-op for opaque pixels) before blending.
+            let (b, g, r) = (
+                unpremultiply(self.data[i], a),
+                unpremultiply(self.data[i + 1], a),
+                unpremultiply(self.data[i + 2], a),
+            );
+            let src_alpha = (a as f64 / 255.0) * self.opacity;
+            let out_alpha = composite_alpha(src_alpha, bg_alpha);
+            out.push(composite_channel(b, self.background.b, src_alpha, bg_alpha));
+            out.push(composite_channel(g, self.background.g, src_alpha, bg_alpha));
+            out.push(composite_channel(r, self.background.r, src_alpha, bg_alpha));
+            out.push((out_alpha * 255.0).round() as u8);
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageFormat {
+    Jpeg,
+    Png,
+    Avif,
+}
+
+impl ImageFormat {
+    fn from_path(path: &str) -> Result<ImageFormat, Box<dyn Error>> {
+        match Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("jpg") | Some("jpeg") => Ok(ImageFormat::Jpeg),
+            Some("png") => Ok(ImageFormat::Png),
+            Some("avif") => Ok(ImageFormat::Avif),
+            _ => Err(format!("Unrecognized image format: {}", path).into()),
+        }
+    }
+}
+
+fn decode_png<R>(r: R) -> Result<ImageSurface, Box<dyn Error>>
+where
+    R: io::Read,
+{
+    Ok(ImageSurface::create_from_png(&mut io::BufReader::new(r))?)
+}
+
+fn encode_png<W>(w: W, surface: ImageSurface) -> Result<(), Box<dyn Error>>
+where
+    W: io::Write,
+{
+    surface.write_to_png(&mut io::BufWriter::new(w))?;
+    Ok(())
+}
+
+fn l8_to_rgb(l: u8) -> (u8, u8, u8) {
+    (l, l, l)
+}
+
+/// Converts Adobe-style (already-inverted) CMYK bytes to RGB.
+fn cmyk_to_rgb(c: u8, m: u8, y: u8, k: u8) -> (u8, u8, u8) {
+    let (c, m, y, k) = (c as u32, m as u32, y as u32, k as u32);
+    let r = (c * k / 255) as u8;
+    let g = (m * k / 255) as u8;
+    let b = (y * k / 255) as u8;
+    (r, g, b)
+}
+
 fn decode_jpeg<R>(r: R) -> Result<ImageSurface, Box<dyn Error>>
 where
     R: io::Read,
 {
     let mut decoder = Decoder::new(io::BufReader::new(r));
-    let rgb = decoder.decode()?;
+    let pixels = decoder.decode()?;
     let metadata = decoder.info().ok_or("Failed to get metadata")?;
-    if metadata.pixel_format != PixelFormat::RGB24 {
-        return Err("Unsupported pixel format".into());
-    }
 
     let width = metadata.width as usize;
     let height = metadata.height as usize;
 
     let mut rgba = Vec::with_capacity(width * height * 4);
-    for chunk in rgb.chunks_exact(3) {
-        rgba.push(chunk[2]);
-        rgba.push(chunk[1]);
-        rgba.push(chunk[0]);
-        rgba.push(0);
+    match metadata.pixel_format {
+        PixelFormat::RGB24 => {
+            for chunk in pixels.chunks_exact(3) {
+                rgba.push(chunk[2]);
+                rgba.push(chunk[1]);
+                rgba.push(chunk[0]);
+                rgba.push(0);
+            }
+        }
+        PixelFormat::L8 => {
+            for &l in pixels.iter() {
+                let (r, g, b) = l8_to_rgb(l);
+                rgba.push(b);
+                rgba.push(g);
+                rgba.push(r);
+                rgba.push(0);
+            }
+        }
+        PixelFormat::CMYK32 => {
+            for chunk in pixels.chunks_exact(4) {
+                let (r, g, b) = cmyk_to_rgb(chunk[0], chunk[1], chunk[2], chunk[3]);
+                rgba.push(b);
+                rgba.push(g);
+                rgba.push(r);
+                rgba.push(0);
+            }
+        }
+        other => return Err(format!("Unsupported pixel format: {:?}", other).into()),
     }
 
     let surface = ImageSurface::create_for_data(
@@ -105,42 +407,260 @@ where
     Ok(surface)
 }
 
-fn encode_jpeg<W>(w: W, surface: ImageSurface, quality: u8) -> Result<(), Box<dyn Error>>
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = 128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b;
+    (y.round() as u8, cb.round() as u8, cr.round() as u8)
+}
+
+/// Adapts a `PixelSource` to `jpeg_encoder`'s row-callback `ImageBuffer`
+/// trait, converting one BGRA scanline to YCbCr planes at a time so the
+/// encoder never needs the whole image in memory.
+struct JpegRowSource<'a, S: PixelSource> {
+    source: &'a S,
+}
+
+impl<'a, S: PixelSource> jpeg_encoder::ImageBuffer for JpegRowSource<'a, S> {
+    fn get_jpeg_color_type(&self) -> jpeg_encoder::JpegColorType {
+        jpeg_encoder::JpegColorType::Ycbcr
+    }
+
+    fn width(&self) -> u16 {
+        self.source.width() as u16
+    }
+
+    fn height(&self) -> u16 {
+        self.source.height() as u16
+    }
+
+    fn fill_buffers(&self, y: usize, buffers: &mut [Vec<u8>]) {
+        for px in self.source.row(y).chunks_exact(4) {
+            let (y, cb, cr) = rgb_to_ycbcr(px[2], px[1], px[0]);
+            buffers[0].push(y);
+            buffers[1].push(cb);
+            buffers[2].push(cr);
+        }
+    }
+}
+
+fn encode_jpeg<W, S>(w: W, source: &S, quality: u8) -> Result<(), Box<dyn Error>>
 where
     W: io::Write,
+    S: PixelSource,
 {
     let encoder = Encoder::new(io::BufWriter::new(w), quality);
-    let width = surface.width() as u16;
-    let height = surface.height() as u16;
-    let data = surface.take_data()?;
-    encoder.encode(data.as_ref(), width, height, jpeg_encoder::ColorType::Bgra)?;
+    encoder.encode_image(JpegRowSource { source })?;
+    Ok(())
+}
+
+/// Undoes premultiplied alpha: given a premultiplied channel byte and the
+/// pixel's alpha, returns the straight (non-premultiplied) channel byte.
+fn unpremultiply(c: u8, a: u8) -> u8 {
+    if a == 0 {
+        0
+    } else {
+        (((c as u32 * 255) + (a as u32 / 2)) / a as u32).min(255) as u8
+    }
+}
+
+fn encode_avif<W>(
+    mut w: W,
+    surface: ImageSurface,
+    quality: u8,
+    pixel_format: AvifPixelFormat,
+) -> Result<(), Box<dyn Error>>
+where
+    W: io::Write,
+{
+    let width = surface.width() as usize;
+    let height = surface.height() as usize;
+    let bgra = surface.take_data()?;
+
+    // `surface` is cairo's ARGB32, which stores premultiplied alpha; ravif
+    // wants straight alpha, so undo the premultiplication per channel.
+    let rgba: Vec<RGBA8> = bgra
+        .chunks_exact(4)
+        .map(|px| {
+            let (b, g, r, a) = (px[0], px[1], px[2], px[3]);
+            RGBA8::new(unpremultiply(r, a), unpremultiply(g, a), unpremultiply(b, a), a)
+        })
+        .collect();
+
+    let image = AvifEncoder::new()
+        .with_quality(quality as f32)
+        .with_internal_color_space(pixel_format.into())
+        .encode_rgba(Img::new(rgba.as_slice(), width, height))?;
+
+    w.write_all(&image.avif_file)?;
     Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let photo = decode_jpeg(fs::File::open(&args.src)?)?;
+    let photo = match ImageFormat::from_path(&args.src)? {
+        ImageFormat::Jpeg => decode_jpeg(fs::File::open(&args.src)?)?,
+        ImageFormat::Png => decode_png(fs::File::open(&args.src)?)?,
+        ImageFormat::Avif => return Err("AVIF input is not supported".into()),
+    };
+
+    let dst_format = ImageFormat::from_path(&args.dst)?;
+    let composite = Composite::new(
+        photo,
+        args.background.clone(),
+        args.opacity,
+        dst_format != ImageFormat::Jpeg,
+    )?;
+
+    match dst_format {
+        ImageFormat::Jpeg => encode_jpeg(fs::File::create(&args.dst)?, &composite, args.quality)?,
+        ImageFormat::Png => encode_png(fs::File::create(&args.dst)?, composite.to_surface()?)?,
+        ImageFormat::Avif => encode_avif(
+            fs::File::create(&args.dst)?,
+            composite.to_surface()?,
+            args.quality,
+            args.pixel_format,
+        )?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let surface = ImageSurface::create(Format::ARgb32, photo.width(), photo.height())?;
-    {
-        let ctx = Context::new(&surface)?;
+    #[test]
+    fn from_hex_rejects_non_ascii_instead_of_panicking() {
+        assert!("#ab\u{1F600}".parse::<Color>().is_err());
+    }
 
-        ctx.save()?;
-        ctx.set_source_rgb(
-            args.background.r(),
-            args.background.g(),
-            args.background.b(),
-        );
-        ctx.rectangle(0.0, 0.0, surface.width() as f64, surface.height() as f64);
-        ctx.fill()?;
-        ctx.restore()?;
+    #[test]
+    fn l8_to_rgb_replicates_luma_into_every_channel() {
+        assert_eq!(l8_to_rgb(0), (0, 0, 0));
+        assert_eq!(l8_to_rgb(128), (128, 128, 128));
+        assert_eq!(l8_to_rgb(255), (255, 255, 255));
+    }
 
-        ctx.set_source_surface(photo, 0.0, 0.0)?;
-        ctx.paint_with_alpha(args.opacity)?;
+    #[test]
+    fn cmyk_to_rgb_zero_raw_key_is_black() {
+        assert_eq!(cmyk_to_rgb(255, 255, 255, 0), (0, 0, 0));
     }
 
-    encode_jpeg(fs::File::create(&args.dst)?, surface, args.quality)?;
+    #[test]
+    fn cmyk_to_rgb_full_key_and_channel_is_saturated() {
+        assert_eq!(cmyk_to_rgb(255, 0, 0, 255), (255, 0, 0));
+    }
 
-    Ok(())
+    #[test]
+    fn cmyk_to_rgb_scales_by_key() {
+        assert_eq!(cmyk_to_rgb(255, 255, 255, 128), (128, 128, 128));
+    }
+
+    #[test]
+    fn from_str_parses_shorthand_hex() {
+        let c = "#0f8".parse::<Color>().unwrap();
+        assert_eq!((c.r, c.g, c.b, c.a), (0x00, 0xff, 0x88, 255));
+    }
+
+    #[test]
+    fn from_str_parses_rgba_hex() {
+        let c = "#11223380".parse::<Color>().unwrap();
+        assert_eq!((c.r, c.g, c.b, c.a), (0x11, 0x22, 0x33, 0x80));
+    }
+
+    #[test]
+    fn from_str_parses_named_colors_case_insensitively() {
+        let c = "White".parse::<Color>().unwrap();
+        assert_eq!((c.r, c.g, c.b, c.a), (255, 255, 255, 255));
+    }
+
+    #[test]
+    fn from_str_parses_transparent() {
+        let c = "transparent".parse::<Color>().unwrap();
+        assert_eq!(c.a, 0);
+    }
+
+    #[test]
+    fn from_str_rejects_unknown_names_and_bad_hex() {
+        assert!("not-a-color".parse::<Color>().is_err());
+        assert!("#12".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn display_compresses_to_shorthand_when_possible() {
+        let c = Color {
+            r: 0x00,
+            g: 0xff,
+            b: 0x88,
+            a: 255,
+        };
+        assert_eq!(c.to_string(), "#0f8");
+    }
+
+    #[test]
+    fn display_falls_back_to_long_form_when_not_shorthand_representable() {
+        let c = Color {
+            r: 0x01,
+            g: 0x02,
+            b: 0x03,
+            a: 255,
+        };
+        assert_eq!(c.to_string(), "#010203");
+    }
+
+    #[test]
+    fn display_includes_alpha_when_translucent() {
+        let c = Color {
+            r: 0xff,
+            g: 0xff,
+            b: 0xff,
+            a: 0x80,
+        };
+        assert_eq!(c.to_string(), "#ffffff80");
+    }
+
+    #[test]
+    fn composite_opaque_background_and_source_passes_source_through() {
+        let src_alpha = 1.0;
+        let bg_alpha = 1.0;
+        assert_eq!(composite_channel(77, 10, src_alpha, bg_alpha), 77);
+        assert_eq!(composite_alpha(src_alpha, bg_alpha), 1.0);
+    }
+
+    #[test]
+    fn composite_zero_opacity_source_shows_translucent_background() {
+        // opacity == 0 means the source contributes nothing; a background
+        // with a=51 (51 / 255 == 0.2) should still show through at 0.2 alpha.
+        let src_alpha = 0.0;
+        let bg_alpha = 51.0 / 255.0;
+        assert_eq!(composite_channel(123, 200, src_alpha, bg_alpha), 40);
+        assert_eq!((composite_alpha(src_alpha, bg_alpha) * 255.0).round() as u8, 51);
+    }
+
+    #[test]
+    fn composite_translucent_source_over_translucent_background() {
+        // opacity 0.5 over an opaque source (src_alpha = 0.5) atop a
+        // background with a=51 (bg_alpha == 0.2): hand-computed expected
+        // premultiplied channel and output alpha bytes.
+        let src_alpha = 0.5;
+        let bg_alpha = 51.0 / 255.0;
+        assert_eq!(composite_channel(200, 50, src_alpha, bg_alpha), 105);
+        assert_eq!((composite_alpha(src_alpha, bg_alpha) * 255.0).round() as u8, 153);
+    }
+
+    #[test]
+    fn unpremultiply_zero_alpha_is_zero() {
+        assert_eq!(unpremultiply(123, 0), 0);
+    }
+
+    #[test]
+    fn unpremultiply_recovers_straight_channel() {
+        // straight 200 premultiplied by alpha 0.5 (a=128) rounds to 100;
+        // un-premultiplying 100 at a=128 should recover ~200.
+        assert_eq!(unpremultiply(100, 128), 199);
+        assert_eq!(unpremultiply(255, 255), 255);
+    }
 }